@@ -1,8 +1,12 @@
 use std::borrow::{Borrow, BorrowMut};
 use std::borrow::Cow::{Borrowed, Owned};
-use std::cell::Cell;
+use std::cell::{Cell, RefCell, UnsafeCell};
+use std::cmp::max;
 use std::marker::{PhantomData, Unsize};
+use std::mem::{align_of, size_of, MaybeUninit};
 use std::ops::{CoerceUnsized, Deref};
+use std::ptr;
+use std::rc::Rc;
 use std::sync::atomic::AtomicIsize;
 
 use typed_arena::Arena;
@@ -187,6 +191,56 @@ impl<'input, TF: TokenFactory<'input, Tok=Box<T>, Inner=T> + Default, T: Token +
     }
 }
 
+impl<'input, TF: TokenFactory<'input, Tok=Box<T>, Inner=T> + Default, T: Token + Clone + 'input> ArenaFactory<'input, TF, T> {
+    /// Rough number of input bytes that map to a single token. Used by
+    /// [`with_capacity_for_source`](Self::with_capacity_for_source) to turn a
+    /// `CharStream::size()` into a token-count estimate.
+    const EST_BYTES_PER_TOKEN: usize = 8;
+
+    /// Creates a factory whose arena is pre-sized for roughly `n` tokens,
+    /// avoiding the repeated chunk reallocations an empty arena performs while
+    /// a large file is lexed. The arena still grows by doubling chunk capacity
+    /// past this initial reservation, keeping amortized allocation O(1).
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            arena: Arena::with_capacity(n),
+            factory: Default::default(),
+            pd: Default::default(),
+        }
+    }
+
+    /// Pre-sizes the factory for a source of `size` characters using the
+    /// [`EST_BYTES_PER_TOKEN`](Self::EST_BYTES_PER_TOKEN) heuristic. Intended
+    /// to be called by the lexer driver with `CharStream::size()`.
+    pub fn with_capacity_for_source(size: isize) -> Self {
+        let tokens = (size.max(0) as usize) / Self::EST_BYTES_PER_TOKEN;
+        Self::with_capacity(tokens + 1)
+    }
+
+    /// Pre-sizes the factory directly from the source stream by reading
+    /// [`CharStream::size`], so the arena is sized for the input before the
+    /// first token is lexed. The lexer driver does not call it: a factory built
+    /// with `ArenaFactory::default()` still starts with an empty arena, and
+    /// pre-sizing happens only where a caller constructs the factory through
+    /// this function.
+    pub fn for_source<'b: 'input>(source: &dyn CharStream<'b>) -> Self {
+        Self::with_capacity_for_source(source.size())
+    }
+
+    /// Reserves room for roughly `n` tokens up front. Must be called before any
+    /// token is created: it replaces the backing arena with one sized for `n`,
+    /// after which the doubling chunk-growth strategy takes over. Calling it
+    /// after tokens exist would drop them; debug builds assert against that,
+    /// release builds do not check.
+    pub fn reserve(&mut self, n: usize) {
+        debug_assert!(
+            self.arena.len() == 0,
+            "ArenaFactory::reserve must be called before any token is created",
+        );
+        self.arena = Arena::with_capacity(n);
+    }
+}
+
 
 impl<'input, TF, T> TokenFactory<'input> for ArenaFactory<'input, TF, T>
     where TF: TokenFactory<'input, Tok=Box<T>, Inner=T>,
@@ -216,6 +270,770 @@ impl<'input, TF, T> TokenFactory<'input> for ArenaFactory<'input, TF, T>
     }
 }
 
+/// Marker for token types that own no heap data and therefore carry no
+/// meaningful destructor, so they may be bump-allocated and forgotten.
+///
+/// Implementing this trait is a promise that dropping a value of `Self` is a
+/// no-op for correctness: a [`DroplessArenaFactory`] never runs the
+/// destructors of the tokens it hands out. Implementing it for a type whose
+/// values may own a `String` (e.g. a [`CommonToken`] built with
+/// [`Owned`](std::borrow::Cow::Owned) text) would leak that `String` for the
+/// lifetime of the arena.
+pub unsafe trait Dropless {}
+
+// SAFETY / LEAK HAZARD: this impl is a promise that `CommonToken` never needs
+// its destructor run when bump-allocated. That holds ONLY when its `text` is
+// `Cow::Borrowed`. The *only* blessed construction path is
+// `DroplessArenaFactory::create`, which refuses owned text (see there). Any
+// other code that bump-allocates a `CommonToken` carrying `Cow::Owned(String)`
+// into a `DroplessArena` will leak that `String` for the arena's lifetime.
+unsafe impl<'a> Dropless for CommonToken<'a> {}
+
+const DROPLESS_MIN_CHUNK: usize = 4096;
+
+/// A dropless bump allocator.
+///
+/// Values are packed into a growing list of byte chunks and the destructor of
+/// `T` is *never* run. Growth doubles the previous chunk capacity so amortized
+/// allocation stays O(1), matching [`ArenaFactory`]'s strategy.
+struct DroplessArena {
+    // Boxed byte chunks kept alive for the lifetime of the arena.
+    chunks: RefCell<Vec<Box<[MaybeUninit<u8>]>>>,
+    // Bump pointers into the current (last) chunk.
+    start: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+}
+
+impl Default for DroplessArena {
+    fn default() -> Self {
+        DroplessArena {
+            chunks: RefCell::new(Vec::new()),
+            start: Cell::new(ptr::null_mut()),
+            end: Cell::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl DroplessArena {
+    /// Rounds `ptr` up to the next multiple of `align` (a power of two).
+    #[inline]
+    fn align_up(ptr: *mut u8, align: usize) -> *mut u8 {
+        let addr = ptr as usize;
+        ((addr + align - 1) & !(align - 1)) as *mut u8
+    }
+
+    /// Pushes a fresh chunk large enough to hold at least `needed` bytes,
+    /// doubling the previous chunk's capacity, and repoints the bump pointers
+    /// at it.
+    #[cold]
+    fn grow(&self, needed: usize) {
+        let mut chunks = self.chunks.borrow_mut();
+        let last_cap = chunks.last().map(|c| c.len()).unwrap_or(0);
+        let cap = max(max(last_cap * 2, needed), DROPLESS_MIN_CHUNK);
+        let mut chunk = vec![MaybeUninit::<u8>::uninit(); cap].into_boxed_slice();
+        let range = chunk.as_mut_ptr_range();
+        self.start.set(range.start as *mut u8);
+        self.end.set(range.end as *mut u8);
+        chunks.push(chunk);
+    }
+
+    /// Bump-allocates `value`, returning a reference valid for as long as the
+    /// arena. The value's destructor will not be run.
+    fn alloc<T>(&self, value: T) -> &mut T {
+        let size = size_of::<T>();
+        let align = align_of::<T>();
+        loop {
+            let ptr = Self::align_up(self.start.get(), align);
+            // `end` is null before the first chunk, so the first iteration
+            // always falls through to `grow`.
+            if !ptr.is_null() && (self.end.get() as usize) >= (ptr as usize) + size {
+                // SAFETY: `ptr` is aligned for `T`, points into the current
+                // chunk, and has `size` bytes of room before `end`.
+                unsafe {
+                    self.start.set(ptr.add(size));
+                    let ptr = ptr as *mut T;
+                    ptr::write(ptr, value);
+                    return &mut *ptr;
+                }
+            }
+            self.grow(size + align);
+        }
+    }
+}
+
+/// A recorded destructor for a value bump-allocated into a [`DropArena`].
+pub struct DropType {
+    drop_fn: unsafe fn(*mut u8),
+    obj: *mut u8,
+}
+
+/// A bump arena for types that are allocated only a handful of times.
+///
+/// Unlike [`DroplessArena`] it *does* run destructors: each allocation whose
+/// type needs dropping records a `(drop_fn, obj)` pair that is replayed when
+/// the arena is dropped. This is cheaper than a dedicated `TypedArena` per
+/// type when only a few values of that type ever exist.
+#[derive(Default)]
+pub struct DropArena {
+    inner: DroplessArena,
+    drops: RefCell<Vec<DropType>>,
+}
+
+impl DropArena {
+    /// Bump-allocates `value`, recording its destructor when `T` needs one.
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.inner.alloc(value);
+        if std::mem::needs_drop::<T>() {
+            unsafe fn drop_fn<T>(obj: *mut u8) {
+                ptr::drop_in_place(obj as *mut T)
+            }
+            self.drops.borrow_mut().push(DropType {
+                drop_fn: drop_fn::<T>,
+                obj: ptr as *mut T as *mut u8,
+            });
+        }
+        ptr
+    }
+}
+
+impl Drop for DropArena {
+    fn drop(&mut self) {
+        for d in self.drops.get_mut().iter() {
+            // SAFETY: each pair was recorded for a live value of the matching
+            // type bump-allocated into `inner`, and is dropped exactly once.
+            unsafe { (d.drop_fn)(d.obj) }
+        }
+    }
+}
+
+/// Dispatch trait letting [`declare_arena!`] route `alloc::<T>` to the right
+/// sub-arena: a dedicated `TypedArena<T>` for `many` types or the shared
+/// [`DropArena`] for `few` types.
+pub trait ArenaAllocator<T> {
+    fn alloc(&self, value: T) -> &T;
+}
+
+/// Generates a single multi-type parse arena with one owner and one `'input`
+/// lifetime.
+///
+/// Each entry is prefixed with `many` (gets a dedicated `TypedArena<T>`, fast
+/// when there are many allocations) or `few` (bump-allocated into a shared
+/// [`DropArena`], cheaper when there are only a handful). The generated struct
+/// exposes a generic `alloc::<T>(&self, v: T) -> &T` that dispatches through
+/// [`ArenaAllocator`].
+///
+/// ```ignore
+/// declare_arena!(ParseArena<'input>;
+///     many tokens: OwningToken;
+///     many contexts: MyContext<'input>;
+///     few errors: ErrorNode<'input>;
+/// );
+/// ```
+#[macro_export]
+macro_rules! declare_arena {
+    ($name:ident < $lt:lifetime >; $($rest:tt)*) => {
+        $crate::declare_arena!(@munch $name $lt; [] []; $($rest)*);
+    };
+    (@munch $name:ident $lt:lifetime; [$($t:tt)*] [$($f:tt)*]; many $field:ident : $ty:ty; $($rest:tt)*) => {
+        $crate::declare_arena!(@munch $name $lt; [$($t)* ($field : $ty)] [$($f)*]; $($rest)*);
+    };
+    (@munch $name:ident $lt:lifetime; [$($t:tt)*] [$($f:tt)*]; few $field:ident : $ty:ty; $($rest:tt)*) => {
+        $crate::declare_arena!(@munch $name $lt; [$($t)*] [$($f)* ($field : $ty)]; $($rest)*);
+    };
+    (@munch $name:ident $lt:lifetime;
+        [$(($tf:ident : $tty:ty))*] [$(($ff:ident : $fty:ty))*]; ) => {
+        pub struct $name<$lt> {
+            $( $tf: $crate::common_token_factory::TypedArena<$tty>, )*
+            shared: $crate::common_token_factory::DropArena,
+            pd: ::std::marker::PhantomData<&$lt str>,
+        }
+
+        impl<$lt> ::std::default::Default for $name<$lt> {
+            fn default() -> Self {
+                $name {
+                    $( $tf: ::std::default::Default::default(), )*
+                    shared: ::std::default::Default::default(),
+                    pd: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        impl<$lt> $name<$lt> {
+            /// Allocates `v` into the sub-arena dedicated to its type.
+            pub fn alloc<T>(&self, v: T) -> &T
+                where Self: $crate::common_token_factory::ArenaAllocator<T>
+            {
+                $crate::common_token_factory::ArenaAllocator::alloc(self, v)
+            }
+        }
+
+        $(
+            impl<$lt> $crate::common_token_factory::ArenaAllocator<$tty> for $name<$lt> {
+                fn alloc(&self, v: $tty) -> &$tty { self.$tf.alloc(v) }
+            }
+        )*
+
+        $(
+            impl<$lt> $crate::common_token_factory::ArenaAllocator<$fty> for $name<$lt> {
+                fn alloc(&self, v: $fty) -> &$fty { self.shared.alloc(v) }
+            }
+        )*
+    };
+}
+
+/// Alias for the per-type sub-arena used by [`declare_arena!`].
+pub type TypedArena<T> = Arena<T>;
+
+/// A [`TokenFactory`] that bump-allocates borrow-only tokens and never runs
+/// their destructors.
+///
+/// This is a drop-in alternative to [`ArenaFactory`] for workloads that use
+/// [`CowTokenFactory`] with [`Borrowed`](std::borrow::Cow::Borrowed) text. The
+/// inner token type must implement [`Dropless`]; see that trait for the
+/// no-heap-data invariant callers must uphold.
+pub struct DroplessArenaFactory<'input, TF: TokenFactory<'input, Tok = Box<T>, Inner = T>, T: Token + Clone + Dropless + 'input> {
+    arena: DroplessArena,
+    factory: TF,
+    pd: PhantomData<&'input str>,
+}
+
+pub type DroplessArenaCowFactory<'a> = DroplessArenaFactory<'a, CowTokenFactory, CommonToken<'a>>;
+
+impl<'input, TF, T> Default for DroplessArenaFactory<'input, TF, T>
+    where TF: TokenFactory<'input, Tok = Box<T>, Inner = T> + Default,
+          T: Token + Clone + Dropless + 'input
+{
+    fn default() -> Self {
+        Self {
+            arena: Default::default(),
+            factory: Default::default(),
+            pd: Default::default(),
+        }
+    }
+}
+
+impl<'input, TF, T> TokenFactory<'input> for DroplessArenaFactory<'input, TF, T>
+    where TF: TokenFactory<'input, Tok = Box<T>, Inner = T>,
+          T: Token + Clone + Dropless + 'input,
+          for<'a> &'a T: Default
+{
+    type Inner = T;
+    type Tok = &'input T;
+
+    fn create<'b: 'input>(&'input self,
+                          source: Option<&mut dyn CharStream<'b>>,
+                          ttype: isize,
+                          text: Option<String>,
+                          channel: isize,
+                          start: isize,
+                          stop: isize,
+                          line: isize,
+                          column: isize,
+    ) -> Self::Tok {
+        // This factory only allocates borrow-only tokens: explicit text would
+        // produce an `Owned(String)` whose destructor the dropless arena never
+        // runs, leaking the `String`. Refuse it in every build: debug builds
+        // panic on the assertion below, release builds emit the invalid token
+        // rather than silently dropping the caller's text and returning a
+        // bogus empty-text token.
+        if text.is_some() {
+            debug_assert!(
+                false,
+                "DroplessArenaFactory only allocates borrowed-text tokens; pass text via the source CharStream, not as an owned String",
+            );
+            return Self::create_invalid();
+        }
+        let token = self.factory
+            .create(source, ttype, None, channel, start, stop, line, column);
+        self.arena.alloc(*token)
+    }
+
+    fn create_invalid() -> &'input T {
+        <&T as Default>::default()
+    }
+}
+
 pub trait TokenAware<'input> {
     type TF: TokenFactory<'input> + 'input;
 }
+
+/// Unique, non-`Clone` capability branding the tokens of one factory instance.
+///
+/// This atomic-free index-access model is used only by [`SingletonToken`] and
+/// [`SingletonTokenFactory`]. `OwningToken` and `CommonToken` do not use it:
+/// they keep an `AtomicIsize` token index and a `read_only` flag.
+///
+/// The capability works like a `GhostCell` brand: the factory hands out exactly
+/// one `FactoryToken<'brand>`, and a token's index lives in an [`UnsafeCell`].
+/// Reading it borrows the token plus `&FactoryToken` (shared); writing it
+/// borrows the token plus `&mut FactoryToken` (exclusive). Because a single
+/// capability exists per factory, `&mut FactoryToken` statically rules out any
+/// aliasing read, so the raw `UnsafeCell` access is sound with no atomics and
+/// no runtime flag. The invariant `'brand` lifetime is generative (supplied by
+/// [`with_singleton_factory`]), so two factories' tokens have incompatible
+/// brands and cannot be confused.
+pub struct FactoryToken<'brand> {
+    // Invariant in `'brand`; `fn(&) -> &` makes it neither co- nor
+    // contravariant. Not `Clone`/`Copy`, so the capability is unique.
+    _brand: PhantomData<fn(&'brand ()) -> &'brand ()>,
+}
+
+/// A token whose index is accessed only through the matching [`FactoryToken`],
+/// so it needs neither an atomic nor a `read_only` flag.
+pub struct SingletonToken<'brand> {
+    pub token_type: isize,
+    pub channel: isize,
+    pub start: isize,
+    pub stop: isize,
+    pub line: isize,
+    pub column: isize,
+    pub text: String,
+    token_index: UnsafeCell<isize>,
+    _brand: PhantomData<fn(&'brand ()) -> &'brand ()>,
+}
+
+impl<'brand> SingletonToken<'brand> {
+    /// Current token index (`-1` until assigned). Takes `&FactoryToken` so the
+    /// read cannot alias an in-flight [`set_token_index`](Self::set_token_index)
+    /// write, which is what makes the bare [`UnsafeCell`] read sound.
+    pub fn token_index(&self, _guard: &FactoryToken<'brand>) -> isize {
+        // SAFETY: `&FactoryToken` proves no `&mut FactoryToken` (hence no
+        // concurrent write) exists, so this shared read does not alias a write.
+        unsafe { *self.token_index.get() }
+    }
+
+    /// Sets the token index. Requires `&mut FactoryToken`, whose uniqueness
+    /// proves no other access to this factory's tokens is in flight — that
+    /// exclusivity, not the cell type, is what makes the write sound without
+    /// synchronization.
+    pub fn set_token_index(&self, index: isize, _guard: &mut FactoryToken<'brand>) {
+        // SAFETY: `&mut FactoryToken` is the unique capability, so no other
+        // read or write of any token's index can be live for this brand.
+        unsafe { *self.token_index.get() = index }
+    }
+}
+
+/// Factory producing [`SingletonToken`]s branded with `'brand`.
+pub struct SingletonTokenFactory<'brand> {
+    _brand: PhantomData<fn(&'brand ()) -> &'brand ()>,
+}
+
+impl<'brand> SingletonTokenFactory<'brand> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(&self,
+                  ttype: isize,
+                  text: String,
+                  channel: isize,
+                  start: isize,
+                  stop: isize,
+                  line: isize,
+                  column: isize,
+    ) -> SingletonToken<'brand> {
+        SingletonToken {
+            token_type: ttype,
+            channel,
+            start,
+            stop,
+            line,
+            column,
+            text,
+            token_index: UnsafeCell::new(-1),
+            _brand: PhantomData,
+        }
+    }
+}
+
+/// Runs `f` with a freshly branded factory and its unique [`FactoryToken`].
+///
+/// The `for<'brand>` bound makes `'brand` generative: each call gets a distinct
+/// brand that cannot escape the closure, so the capability is genuinely unique
+/// per factory instance.
+pub fn with_singleton_factory<R>(
+    f: impl for<'brand> FnOnce(SingletonTokenFactory<'brand>, FactoryToken<'brand>) -> R,
+) -> R {
+    f(
+        SingletonTokenFactory { _brand: PhantomData },
+        FactoryToken { _brand: PhantomData },
+    )
+}
+
+/// Returned by [`RingProducer::create`] when the producer would overwrite a
+/// slot the consumer has not read yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingFull;
+
+// Shared, `Cell`-synchronized state behind a ring-buffer factory. The `write`
+// and `read` cursors are monotonic; `write - read` is the number of live
+// tokens, which never exceeds `cap`.
+struct RingShared<T> {
+    slots: RefCell<Vec<Option<T>>>,
+    cap: usize,
+    write: Cell<usize>,
+    read: Cell<usize>,
+}
+
+/// A streaming token factory backed by a fixed-capacity ring buffer of
+/// reusable slots.
+///
+/// Unlike [`ArenaFactory`], which keeps every token alive for the whole parse,
+/// this caps memory at `cap` tokens: the producer writes into `write % cap` and
+/// advances `write`, while slots below the consumer's `read` cursor are free
+/// and get overwritten. It trades full random access for a constant footprint,
+/// suited to very large or unbounded streams that only re-read a sliding
+/// window.
+///
+/// Call [`split`](RingTokenFactory::split) to obtain the single-threaded
+/// producer (lexer) and consumer (token stream) handles, which share the
+/// cursors through [`Cell`]s so the buffer self-synchronizes.
+pub struct RingTokenFactory<'input, TF: TokenFactory<'input, Tok = Box<T>, Inner = T>, T: Token + Clone + 'input> {
+    shared: Rc<RingShared<T>>,
+    factory: TF,
+    pd: PhantomData<&'input str>,
+}
+
+/// Producer (lexer-side) handle over a [`RingTokenFactory`].
+pub struct RingProducer<'input, TF: TokenFactory<'input, Tok = Box<T>, Inner = T>, T: Token + Clone + 'input> {
+    shared: Rc<RingShared<T>>,
+    factory: TF,
+    pd: PhantomData<&'input str>,
+}
+
+/// Consumer (token-stream-side) handle over a [`RingTokenFactory`].
+pub struct RingConsumer<T> {
+    shared: Rc<RingShared<T>>,
+}
+
+impl<'input, TF, T> RingTokenFactory<'input, TF, T>
+    where TF: TokenFactory<'input, Tok = Box<T>, Inner = T>,
+          T: Token + Clone + 'input
+{
+    /// Creates a factory whose ring holds at most `cap` tokens.
+    pub fn new(cap: usize, factory: TF) -> Self {
+        assert!(cap > 0, "ring capacity must be non-zero");
+        let mut slots = Vec::with_capacity(cap);
+        slots.resize_with(cap, || None);
+        RingTokenFactory {
+            shared: Rc::new(RingShared {
+                slots: RefCell::new(slots),
+                cap,
+                write: Cell::new(0),
+                read: Cell::new(0),
+            }),
+            factory,
+            pd: PhantomData,
+        }
+    }
+
+    /// Splits into producer and consumer handles sharing the same ring.
+    pub fn split(self) -> (RingProducer<'input, TF, T>, RingConsumer<T>) {
+        let consumer = RingConsumer { shared: self.shared.clone() };
+        let producer = RingProducer {
+            shared: self.shared,
+            factory: self.factory,
+            pd: self.pd,
+        };
+        (producer, consumer)
+    }
+}
+
+impl<'input, TF, T> RingProducer<'input, TF, T>
+    where TF: TokenFactory<'input, Tok = Box<T>, Inner = T>,
+          T: Token + Clone + 'input
+{
+    /// Builds a token and writes it into the next free slot, advancing the
+    /// write cursor. Returns the token's absolute index, or [`RingFull`] when
+    /// the unconsumed window already spans the whole buffer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create<'b: 'input>(&'input self,
+                              source: Option<&mut dyn CharStream<'b>>,
+                              ttype: isize,
+                              text: Option<String>,
+                              channel: isize,
+                              start: isize,
+                              stop: isize,
+                              line: isize,
+                              column: isize,
+    ) -> Result<usize, RingFull> {
+        let shared = &*self.shared;
+        let w = shared.write.get();
+        if w - shared.read.get() >= shared.cap {
+            return Err(RingFull);
+        }
+        let token = self.factory
+            .create(source, ttype, text, channel, start, stop, line, column);
+        shared.slots.borrow_mut()[w % shared.cap] = Some(*token);
+        shared.write.set(w + 1);
+        Ok(w)
+    }
+}
+
+impl<T: Clone> RingConsumer<T> {
+    /// Absolute index of the next token to be consumed.
+    pub fn position(&self) -> usize {
+        self.shared.read.get()
+    }
+
+    /// Number of tokens currently waiting in the ring.
+    pub fn pending(&self) -> usize {
+        self.shared.write.get() - self.shared.read.get()
+    }
+
+    /// Clones the token at absolute `index` if it is still within the live
+    /// window `[read, write)`; slots outside it have been recycled or not yet
+    /// produced.
+    pub fn peek(&self, index: usize) -> Option<T> {
+        let shared = &*self.shared;
+        if index < shared.read.get() || index >= shared.write.get() {
+            return None;
+        }
+        shared.slots.borrow()[index % shared.cap].clone()
+    }
+
+    /// Takes the next token and advances the read cursor, freeing its slot for
+    /// the producer to overwrite.
+    pub fn next(&self) -> Option<T> {
+        let shared = &*self.shared;
+        let r = shared.read.get();
+        if r >= shared.write.get() {
+            return None;
+        }
+        let token = shared.slots.borrow_mut()[r % shared.cap].take();
+        shared.read.set(r + 1);
+        token
+    }
+}
+
+/// `Cow`-free token factory subsystem for `no_std` / allocation-constrained
+/// parsers.
+///
+/// [`CowTokenFactory`] and [`CommonToken`] pull in `std::borrow::Cow` and
+/// `ToOwned`, whose infallible cloning is unacceptable on embedded targets.
+/// Behind the `no_cow` feature this module offers an equivalent factory built
+/// on a hand-rolled [`TokenText`] enum and an explicit [`TextAlloc`] strategy,
+/// so the `borrow` machinery is never instantiated and the owned arm is
+/// whatever buffer the caller supplies.
+///
+/// The feature only adds this separate factory and its [`NoCowToken`]:
+/// `CommonToken` and `CowTokenFactory` still use `Cow` with or without it,
+/// [`TokenFactory::create`] takes no allocation
+/// strategy, and the feature does not make the crate `no_std`.
+/// The tests in this module are compiled only with the `no_cow` feature.
+#[cfg(feature = "no_cow")]
+pub use self::no_cow::*;
+
+#[cfg(feature = "no_cow")]
+mod no_cow {
+    use super::CharStream;
+
+    /// Two-variant replacement for `Cow<'a, str>`. The owned arm `O` is
+    /// configurable so embedded callers can back it with their own buffer type
+    /// instead of `String`.
+    pub enum TokenText<'a, O> {
+        Borrowed(&'a str),
+        Owned(O),
+    }
+
+    impl<'a, O: AsRef<str>> TokenText<'a, O> {
+        /// Borrows the text regardless of which arm holds it.
+        pub fn as_str(&self) -> &str {
+            match self {
+                TokenText::Borrowed(s) => s,
+                TokenText::Owned(o) => o.as_ref(),
+            }
+        }
+    }
+
+    /// Caller-supplied allocation strategy, replacing the implicit
+    /// `str::to_owned()` of the `Cow` factories. An embedded user implements
+    /// this over their own buffer type.
+    pub trait TextAlloc<'a> {
+        /// Owned text buffer this strategy produces.
+        type Owned: AsRef<str>;
+
+        /// Takes ownership of `text`, producing the owned arm of [`TokenText`].
+        fn own(&mut self, text: &str) -> Self::Owned;
+    }
+
+    /// Minimal, `Cow`-free token used by [`NoCowTokenFactory`].
+    pub struct NoCowToken<'a, O> {
+        pub token_type: isize,
+        pub channel: isize,
+        pub start: isize,
+        pub stop: isize,
+        pub line: isize,
+        pub column: isize,
+        pub text: TokenText<'a, O>,
+    }
+
+    /// `no_std`-friendly analogue of [`TokenFactory`](super::TokenFactory)
+    /// whose `create` takes an explicit allocation `strategy` rather than
+    /// implicitly calling `to_owned()`.
+    #[derive(Default)]
+    pub struct NoCowTokenFactory;
+
+    impl NoCowTokenFactory {
+        #[allow(clippy::too_many_arguments)]
+        pub fn create<'a, 'b: 'a, A: TextAlloc<'a>>(
+            &'a self,
+            strategy: &mut A,
+            source: Option<&mut dyn CharStream<'b>>,
+            ttype: isize,
+            text: Option<&'a str>,
+            channel: isize,
+            start: isize,
+            stop: isize,
+            line: isize,
+            column: isize,
+        ) -> NoCowToken<'a, A::Owned> {
+            let text = match (text, source) {
+                (Some(t), _) => TokenText::Owned(strategy.own(t)),
+                (None, Some(x)) => {
+                    let t = if stop >= x.size() || start >= x.size() { "<EOF>" } else { x.get_text(start, stop) };
+                    TokenText::Borrowed(t)
+                }
+                _ => TokenText::Borrowed(""),
+            };
+            NoCowToken {
+                token_type: ttype,
+                channel,
+                start,
+                stop,
+                line,
+                column,
+                text,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // Owned arm backed by `String`; an embedded caller would supply their
+        // own buffer type here instead.
+        struct StringAlloc;
+
+        impl<'a> TextAlloc<'a> for StringAlloc {
+            type Owned = String;
+
+            fn own(&mut self, text: &str) -> String {
+                text.to_owned()
+            }
+        }
+
+        #[test]
+        fn no_cow_factory_uses_explicit_strategy_for_owned_text() {
+            let factory = NoCowTokenFactory::default();
+            let token = factory.create(&mut StringAlloc, None, 1, Some("kw"), 0, 0, 0, 0, 0);
+            assert!(matches!(token.text, TokenText::Owned(_)));
+            assert_eq!(token.text.as_str(), "kw");
+        }
+
+        #[test]
+        fn no_cow_factory_borrows_without_allocating() {
+            let factory = NoCowTokenFactory::default();
+            let token = factory.create(&mut StringAlloc, None, 1, None, 0, 0, 0, 0, 0);
+            assert!(matches!(token.text, TokenText::Borrowed(_)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropless_arena_keeps_values_across_chunk_growth() {
+        // Allocate well past DROPLESS_MIN_CHUNK so several chunks are pushed,
+        // then check every earlier reference is still valid (old chunks are
+        // retained, only the bump pointers move).
+        let arena = DroplessArena::default();
+        let n = (DROPLESS_MIN_CHUNK / size_of::<u64>()) * 4 + 7;
+        let refs: Vec<&mut u64> = (0..n as u64).map(|i| arena.alloc(i)).collect();
+        assert!(arena.chunks.borrow().len() > 1, "expected multiple chunks");
+        for (i, r) in refs.iter().enumerate() {
+            assert_eq!(**r, i as u64);
+        }
+    }
+
+    #[test]
+    fn dropless_arena_respects_alignment() {
+        // Interleaving types of different alignment must never hand back a
+        // misaligned pointer.
+        let arena = DroplessArena::default();
+        for _ in 0..256 {
+            let _byte: &mut u8 = arena.alloc(0xABu8);
+            let word: &mut u64 = arena.alloc(0xDEAD_BEEFu64);
+            assert_eq!((word as *mut u64 as usize) % align_of::<u64>(), 0);
+            assert_eq!(*word, 0xDEAD_BEEF);
+        }
+    }
+
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drop_arena_runs_recorded_destructors_once() {
+        let counter = Rc::new(Cell::new(0));
+        {
+            let arena = DropArena::default();
+            for _ in 0..32 {
+                arena.alloc(DropCounter(counter.clone()));
+            }
+            assert_eq!(arena.drops.borrow().len(), 32);
+            assert_eq!(counter.get(), 0, "destructors must not run before arena drop");
+        }
+        assert_eq!(counter.get(), 32, "each destructor must run exactly once");
+    }
+
+    #[test]
+    fn drop_arena_skips_trivially_droppable_types() {
+        let arena = DropArena::default();
+        for i in 0..10u64 {
+            arena.alloc(i);
+        }
+        assert!(arena.drops.borrow().is_empty(), "types without drop glue record nothing");
+    }
+
+    fn ring_token(producer: &RingProducer<CommonTokenFactory, OwningToken>, text: &str)
+        -> Result<usize, RingFull>
+    {
+        producer.create(None, 1, Some(text.to_owned()), 0, 0, 0, 0, 0)
+    }
+
+    #[test]
+    fn ring_factory_caps_and_recycles_slots() {
+        let (producer, consumer) = RingTokenFactory::new(2, CommonTokenFactory {}).split();
+
+        assert_eq!(ring_token(&producer, "a"), Ok(0));
+        assert_eq!(ring_token(&producer, "b"), Ok(1));
+        assert_eq!(consumer.pending(), 2);
+        // Buffer full: the producer would overtake the unconsumed window.
+        assert_eq!(ring_token(&producer, "c"), Err(RingFull));
+
+        // Consuming frees a slot, so production resumes at the next index.
+        assert_eq!(consumer.next().map(|t| t.text), Some("a".to_owned()));
+        assert_eq!(consumer.position(), 1);
+        assert_eq!(ring_token(&producer, "c"), Ok(2));
+        assert_eq!(consumer.pending(), 2);
+
+        assert_eq!(consumer.next().map(|t| t.text), Some("b".to_owned()));
+        assert_eq!(consumer.next().map(|t| t.text), Some("c".to_owned()));
+        assert_eq!(consumer.next().map(|t| t.text), None);
+    }
+
+    #[test]
+    fn singleton_token_index_round_trips_through_guard() {
+        with_singleton_factory(|factory, mut guard| {
+            let token = factory.create(1, "x".to_owned(), 0, 0, 0, 0, 0);
+            assert_eq!(token.token_index(&guard), -1);
+            token.set_token_index(42, &mut guard);
+            assert_eq!(token.token_index(&guard), 42);
+        });
+    }
+}